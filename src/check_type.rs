@@ -1,5 +1,6 @@
+use std::collections::HashMap;
+
 use crate::{
-    array::Shape,
     function::{Function, Instr},
     primitive::Primitive,
     value::Value,
@@ -11,21 +12,42 @@ pub enum ValueType {
     Char,
     Function,
     Box(Box<Type>),
+    /// One of several possible types, as when a boxed array holds elements
+    /// of more than one type. Always has at least two distinct members.
+    Union(Vec<Type>),
+    /// A synthesized [`signature`] input whose type hasn't been constrained
+    /// yet. The `u32` is its index into [`TypeEnv::inputs`], so a later
+    /// instruction that learns something about it (e.g. `Add` requiring
+    /// `Num`) can write the discovery back onto the recorded input instead
+    /// of a detached clone.
+    Var(u32),
     #[default]
     Unknown,
 }
 
+/// A single shape dimension, either a concrete extent or a variable standing
+/// in for an extent that isn't known until the array is actually built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dim {
+    Known(usize),
+    Var(u32),
+}
+
+/// A shape made of [`Dim`]s rather than concrete `usize`s, so a rank can be
+/// tracked even when some (or all) of its extents are data-dependent.
+pub type TypeShape = Vec<Dim>;
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Type {
     pub value: ValueType,
-    pub shape: Option<Shape>,
+    pub shape: Option<TypeShape>,
 }
 
 impl Type {
     pub fn new(value: ValueType, shape: impl IntoIterator<Item = usize>) -> Self {
         Self {
             value,
-            shape: Some(shape.into_iter().collect()),
+            shape: Some(shape.into_iter().map(Dim::Known).collect()),
         }
     }
     pub fn unknown_shape(value: ValueType) -> Self {
@@ -44,14 +66,43 @@ impl Type {
                     }
                 }
             },
-            shape: Some(Shape::from(val.shape())),
+            shape: Some(val.shape().iter().copied().map(Dim::Known).collect()),
         }
     }
 }
 
-type TypeResult<T> = Option<T>;
+/// What went wrong while checking a single [`Instr`], without the location
+/// information that [`TypeError`] adds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeErrorKind {
+    /// A value was popped off a stack (the value stack or the array-nesting
+    /// stack) that didn't have anything on it.
+    StackUnderflow,
+    /// A `Type` was required to match some expectation and didn't.
+    Mismatch { expected: Type, found: Type },
+    /// An instruction has no type rule, either because it is not yet
+    /// supported or because it can never be typed statically.
+    UnhandledInstr,
+    /// A shape had a different rank than required: two shapes that had to
+    /// unify differed in rank, or `Len`/`First` got a rank-0 operand.
+    RankMismatch { expected: usize, found: usize },
+    /// Two dimensions were required to unify (e.g. for a pervasive dyadic
+    /// primitive, or for the two arrays joined by `Couple`) but disagreed.
+    /// Elements of an array literal that fail to unify do not raise this;
+    /// the literal's shape is left unknown instead.
+    ShapeConflict { a: Dim, b: Dim },
+}
+
+/// A [`TypeErrorKind`] located at the instruction that produced it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub instr_index: usize,
+    pub kind: TypeErrorKind,
+}
+
+type TypeResult<T> = Result<T, TypeErrorKind>;
 
-pub fn check_type(f: &Function, inputs: &[Value]) -> TypeResult<Vec<Type>> {
+pub fn check_type(f: &Function, inputs: &[Value]) -> Result<Vec<Type>, TypeError> {
     let mut stack = Vec::new();
     for input in inputs {
         stack.push(Type::from_value(input));
@@ -59,16 +110,80 @@ pub fn check_type(f: &Function, inputs: &[Value]) -> TypeResult<Vec<Type>> {
     let mut env = TypeEnv {
         stack,
         array: Vec::new(),
+        subst: HashMap::new(),
+        next_var: 0,
+        inputs: None,
+        value_subst: HashMap::new(),
+        rank_vars: HashMap::new(),
+    };
+    for (instr_index, instr) in f.instrs.iter().enumerate() {
+        env.instr(instr)
+            .map_err(|kind| TypeError { instr_index, kind })?;
+    }
+    Ok(env.stack)
+}
+
+/// Infers the stack signature of `f` with no inputs given, using
+/// bidirectional inference: starting from an empty stack, every time an
+/// instruction tries to `pop` something that isn't there, a fresh
+/// [`ValueType::Var`] is synthesized, recorded as a required input, and
+/// handed back as if it had already been on the stack. Unlike a plain
+/// `Unknown`, a `Var` is a handle onto its recorded input: when a later
+/// instruction constrains it (e.g. `Add` requiring `Num`, or `Len` requiring
+/// at least one dim), that constraint is written back onto the input
+/// itself, so the signature reflects it. Shape constraints are recorded only
+/// when `Len`/`First` commit an input to rank 1; an input combined with a
+/// known-shaped array by a dyadic primitive or `Couple` keeps an unknown
+/// shape, and the rank placeholder minted by `Shape` is not written back
+/// onto the input. Once every instr has been walked, the synthesized inputs (in
+/// reverse pop order, since the first thing popped is the *last* argument
+/// the caller pushed) are the function's argument types, and whatever
+/// remains on the stack is its result types.
+pub fn signature(f: &Function) -> Result<(Vec<Type>, Vec<Type>), TypeError> {
+    let mut env = TypeEnv {
+        stack: Vec::new(),
+        array: Vec::new(),
+        subst: HashMap::new(),
+        next_var: 0,
+        inputs: Some(Vec::new()),
+        value_subst: HashMap::new(),
+        rank_vars: HashMap::new(),
     };
-    for instr in &f.instrs {
-        env.instr(instr)?;
+    for (instr_index, instr) in f.instrs.iter().enumerate() {
+        env.instr(instr)
+            .map_err(|kind| TypeError { instr_index, kind })?;
+    }
+    let mut inputs = env.inputs.take().unwrap_or_default();
+    inputs.reverse();
+    for ty in &mut inputs {
+        env.resolve_type_in_place(ty);
     }
-    Some(env.stack)
+    let mut outputs = std::mem::take(&mut env.stack);
+    for ty in &mut outputs {
+        env.resolve_type_in_place(ty);
+    }
+    Ok((inputs, outputs))
 }
 
 struct TypeEnv {
     stack: Vec<Type>,
     array: Vec<usize>,
+    /// Bindings discovered while unifying [`Dim::Var`]s against other dims.
+    subst: HashMap<u32, Dim>,
+    /// Counter for minting fresh [`Dim::Var`]s.
+    next_var: u32,
+    /// When present, a `pop` against an empty stack synthesizes a fresh
+    /// input type instead of failing, and appends it here in the order it
+    /// was needed. Used by [`signature`] to recover a function's argument
+    /// types without being given any concrete inputs.
+    inputs: Option<Vec<Type>>,
+    /// Bindings discovered for [`ValueType::Var`]s, the same way `subst`
+    /// tracks bindings for [`Dim::Var`]s.
+    value_subst: HashMap<u32, ValueType>,
+    /// The [`Dim`] standing in for a synthesized input's rank (the length
+    /// `Shape` would report), minted the first time `Shape` is applied to
+    /// an input whose shape is still completely unconstrained.
+    rank_vars: HashMap<u32, Dim>,
 }
 
 impl TypeEnv {
@@ -77,54 +192,449 @@ impl TypeEnv {
             Instr::Push(val) => self.push(Type::from_value(val)),
             Instr::BeginArray => self.array.push(self.stack.len()),
             Instr::EndArray { boxed, .. } => {
-                let bottom = self.array.pop()?;
+                let bottom = self.array.pop().ok_or(TypeErrorKind::StackUnderflow)?;
                 let items = self.stack.split_off(bottom);
-                let value = if items.windows(2).all(|w| w[0] == w[1]) {
-                    items.get(0).map(|ty| ty.value.clone()).unwrap_or_else(|| {
-                        if *boxed {
-                            ValueType::Unknown
-                        } else {
-                            ValueType::Num
-                        }
-                    })
-                } else {
-                    ValueType::Unknown
-                };
                 let (value, shape) = if *boxed {
-                    let value = ValueType::Box(Box::new(Type::unknown_shape(value)));
-                    (value, Some(Shape::from_iter([items.len()])))
+                    let value = ValueType::Box(Box::new(Self::box_element_type(&items)));
+                    (value, Some(vec![Dim::Known(items.len())]))
                 } else {
-                    let mut shape = if items.windows(2).all(|w| w[0].shape == w[1].shape) {
-                        items.get(0).and_then(|ty| ty.shape.clone())
+                    let value = if items.windows(2).all(|w| w[0] == w[1]) {
+                        items
+                            .get(0)
+                            .map(|ty| ty.value.clone())
+                            .unwrap_or(ValueType::Num)
                     } else {
-                        None
+                        ValueType::Unknown
                     };
+                    let mut shapes = items.iter().map(|ty| ty.shape.clone());
+                    let mut shape = shapes.next().flatten();
+                    for next in shapes {
+                        shape = match (shape, next) {
+                            (Some(a), Some(b)) => self.unify_shape(a, b).ok(),
+                            _ => None,
+                        };
+                    }
                     if let Some(shape) = &mut shape {
-                        shape.insert(0, items.len());
+                        shape.insert(0, Dim::Known(items.len()));
                     }
                     (value, shape)
                 };
                 self.push(Type { value, shape });
             }
-            _ => return None,
+            Instr::Prim(prim, _) => self.prim(*prim)?,
+            _ => return Err(TypeErrorKind::UnhandledInstr),
         }
-        Some(())
+        Ok(())
     }
     fn prim(&mut self, prim: Primitive) -> TypeResult<()> {
         use Primitive::*;
         match prim {
-            _ => return None,
+            Add | Sub | Mul | Div | Mod | Pow | Min | Max | Eq | Ne | Lt | Le | Gt | Ge => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let a_val = self.resolve_value(a.value.clone());
+                let b_val = self.resolve_value(b.value.clone());
+                // These primitives only ever accept `Num`s, so an operand
+                // whose type isn't pinned down yet (`Unknown`, including a
+                // synthesized input that resolved to it) is *forced* to
+                // `Num` rather than left open.
+                let value = match (&a_val, &b_val) {
+                    (ValueType::Num, ValueType::Num)
+                    | (ValueType::Unknown, ValueType::Num)
+                    | (ValueType::Num, ValueType::Unknown)
+                    | (ValueType::Unknown, ValueType::Unknown) => ValueType::Num,
+                    _ => {
+                        return Err(TypeErrorKind::Mismatch {
+                            expected: Type::unknown_shape(ValueType::Num),
+                            found: if matches!(a_val, ValueType::Num | ValueType::Unknown) {
+                                b
+                            } else {
+                                a
+                            },
+                        })
+                    }
+                };
+                self.constrain_value(&a, value.clone());
+                self.constrain_value(&b, value.clone());
+                let shape = self.broadcast_shapes(a.shape, b.shape)?;
+                self.push(Type { value, shape });
+            }
+            Neg | Not | Sqrt | Abs | Sign | Floor | Round => {
+                let mut a = self.pop()?;
+                let value = match self.resolve_value(a.value.clone()) {
+                    ValueType::Num | ValueType::Unknown => ValueType::Num,
+                    found => {
+                        return Err(TypeErrorKind::Mismatch {
+                            expected: Type::unknown_shape(ValueType::Num),
+                            found: Type {
+                                value: found,
+                                shape: a.shape,
+                            },
+                        })
+                    }
+                };
+                self.constrain_value(&a, value.clone());
+                a.value = value;
+                self.push(a);
+            }
+            Reverse | Fix => {
+                let a = self.pop()?;
+                self.push(a);
+            }
+            Transpose => {
+                let mut a = self.pop()?;
+                if let Some(shape) = &mut a.shape {
+                    // Uiua's transpose cyclically moves the first axis to
+                    // the end, not just the first two (a plain swap only
+                    // happens to match that at rank 2).
+                    if shape.len() >= 2 {
+                        shape.rotate_left(1);
+                    }
+                }
+                self.push(a);
+            }
+            Rotate => {
+                let by = self.pop()?;
+                let a = self.pop()?;
+                let by_val = self.resolve_value(by.value.clone());
+                if by_val != ValueType::Num && by_val != ValueType::Unknown {
+                    return Err(TypeErrorKind::Mismatch {
+                        expected: Type::unknown_shape(ValueType::Num),
+                        found: by,
+                    });
+                }
+                self.constrain_value(&by, ValueType::Num);
+                self.push(a);
+            }
+            Shape => {
+                let a = self.pop()?;
+                let rank = match &a.shape {
+                    Some(shape) => Dim::Known(shape.len()),
+                    // Its shape hasn't been constrained by anything else
+                    // yet; mint a placeholder for the (still unknown) rank
+                    // rather than erroring, so `signature` can still return
+                    // something for functions that only inspect `Shape`.
+                    None => match a.value {
+                        ValueType::Var(v) => self.rank_var(v),
+                        _ => {
+                            return Err(TypeErrorKind::Mismatch {
+                                expected: Type::new(ValueType::Unknown, []),
+                                found: a,
+                            })
+                        }
+                    },
+                };
+                self.push(Type {
+                    value: ValueType::Num,
+                    shape: Some(vec![rank]),
+                });
+            }
+            Len | First => {
+                let mut a = self.pop()?;
+                if a.shape.is_none() {
+                    // `Len`/`First` need at least one leading dim; commit a
+                    // synthesized input to rank 1 (a vector) the first time
+                    // that's demanded, instead of erroring outright.
+                    self.ensure_rank(&mut a, 1)?;
+                }
+                let mut shape = a.shape.take().unwrap();
+                if shape.is_empty() {
+                    return Err(TypeErrorKind::RankMismatch {
+                        expected: 1,
+                        found: 0,
+                    });
+                }
+                shape.remove(0);
+                a.shape = Some(shape);
+                self.push(a);
+            }
+            Couple => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let a_val = self.resolve_value(a.value.clone());
+                let b_val = self.resolve_value(b.value.clone());
+                let value = match (&a_val, &b_val) {
+                    (ValueType::Unknown, _) => b_val.clone(),
+                    (_, ValueType::Unknown) => a_val.clone(),
+                    (x, y) if x == y => a_val.clone(),
+                    (ValueType::Union(variants), other) | (other, ValueType::Union(variants))
+                        if variants.iter().any(|v| &v.value == other) =>
+                    {
+                        ValueType::Union(variants.clone())
+                    }
+                    _ => {
+                        return Err(TypeErrorKind::Mismatch {
+                            expected: a.clone(),
+                            found: b,
+                        })
+                    }
+                };
+                self.constrain_value(&a, value.clone());
+                self.constrain_value(&b, value.clone());
+                let shape = match (a.shape, b.shape) {
+                    (Some(sa), Some(sb)) => {
+                        let mut shape = self.unify_shape(sa, sb)?;
+                        shape.insert(0, Dim::Known(2));
+                        Some(shape)
+                    }
+                    _ => None,
+                };
+                self.push(Type { value, shape });
+            }
+            Unbox => {
+                let a = self.pop()?;
+                let inner = self.unbox_type(a)?;
+                self.push(inner);
+            }
+            Reshape => {
+                let new_shape = self.pop()?;
+                let data = self.pop()?;
+                let rank = new_shape
+                    .shape
+                    .as_ref()
+                    .and_then(|s| s.get(0))
+                    .and_then(|d| match d {
+                        Dim::Known(n) => Some(*n),
+                        Dim::Var(_) => None,
+                    });
+                let shape = rank.map(|rank| (0..rank).map(|_| self.fresh_dim()).collect());
+                self.push(Type {
+                    value: data.value,
+                    shape,
+                });
+            }
+            _ => return Err(TypeErrorKind::UnhandledInstr),
+        }
+        Ok(())
+    }
+    /// The element type of a boxed array literal: if every item has the
+    /// same `Type` that type is used directly, otherwise the distinct
+    /// types are kept as a [`ValueType::Union`] instead of being collapsed
+    /// to `Unknown`.
+    fn box_element_type(items: &[Type]) -> Type {
+        let mut distinct: Vec<Type> = Vec::new();
+        for item in items {
+            if !distinct.contains(item) {
+                distinct.push(item.clone());
+            }
+        }
+        match distinct.len() {
+            0 => Type::unknown_shape(ValueType::Unknown),
+            1 => distinct.into_iter().next().unwrap(),
+            _ => Type::unknown_shape(ValueType::Union(distinct)),
+        }
+    }
+    /// Unwraps one layer of boxing from `ty`. If `ty` is a union of boxed
+    /// alternatives, the result is the (deduplicated) union of their
+    /// contents rather than a single arbitrary branch, so unboxing a union
+    /// still yields a union instead of erasing the alternatives.
+    fn unbox_type(&self, ty: Type) -> TypeResult<Type> {
+        match self.resolve_value(ty.value) {
+            ValueType::Box(inner) => Ok(*inner),
+            ValueType::Union(variants) => {
+                let mut contents: Vec<Type> = Vec::new();
+                for variant in variants {
+                    let inner = self.unbox_type(variant)?;
+                    if !contents.contains(&inner) {
+                        contents.push(inner);
+                    }
+                }
+                Ok(if contents.len() == 1 {
+                    contents.into_iter().next().unwrap()
+                } else {
+                    Type::unknown_shape(ValueType::Union(contents))
+                })
+            }
+            found => Err(TypeErrorKind::Mismatch {
+                expected: Type::unknown_shape(ValueType::Box(Box::new(Type::unknown_shape(
+                    ValueType::Unknown,
+                )))),
+                found: Type {
+                    value: found,
+                    shape: ty.shape,
+                },
+            }),
         }
-        Some(())
     }
     fn push(&mut self, ty: Type) {
         self.stack.push(ty);
     }
-    fn pop(&mut self) -> Type {
-        let val = self.stack.pop().unwrap_or_default();
+    fn pop(&mut self) -> TypeResult<Type> {
+        let val = match self.stack.pop() {
+            Some(val) => val,
+            None => match &mut self.inputs {
+                Some(inputs) => {
+                    let idx = inputs.len() as u32;
+                    let ty = Type::unknown_shape(ValueType::Var(idx));
+                    inputs.push(ty.clone());
+                    ty
+                }
+                None => return Err(TypeErrorKind::StackUnderflow),
+            },
+        };
         for arr in &mut self.array {
             *arr = (*arr).min(self.stack.len());
         }
-        val
+        Ok(val)
+    }
+    /// Recursively replaces any [`ValueType::Var`] still left in `ty` (one
+    /// that no primitive ever pinned down) with its resolved value, so the
+    /// internal variable tag never leaks out of [`signature`]'s result.
+    fn resolve_type_in_place(&self, ty: &mut Type) {
+        ty.value = match std::mem::take(&mut ty.value) {
+            ValueType::Var(v) => self.resolve_value(ValueType::Var(v)),
+            ValueType::Box(mut inner) => {
+                self.resolve_type_in_place(&mut inner);
+                ValueType::Box(inner)
+            }
+            ValueType::Union(mut variants) => {
+                for variant in &mut variants {
+                    self.resolve_type_in_place(variant);
+                }
+                ValueType::Union(variants)
+            }
+            other => other,
+        };
+    }
+    /// Follows a possibly-synthesized value through `self.value_subst`, the
+    /// same way [`resolve_dim`](Self::resolve_dim) follows `Dim::Var`
+    /// chains. An unbound variable resolves to `Unknown`, since nothing has
+    /// constrained it yet.
+    fn resolve_value(&self, value: ValueType) -> ValueType {
+        match value {
+            ValueType::Var(v) => match self.value_subst.get(&v) {
+                Some(bound) => self.resolve_value(bound.clone()),
+                None => ValueType::Unknown,
+            },
+            other => other,
+        }
+    }
+    /// If `ty` is a still-unconstrained synthesized input, records that it
+    /// must be `value`, writing the discovery back onto the recorded input
+    /// so `signature` reflects it. Does nothing for a `ty` that is already
+    /// concrete (the caller has already checked compatibility in that
+    /// case).
+    fn constrain_value(&mut self, ty: &Type, value: ValueType) {
+        if let ValueType::Var(v) = ty.value.clone() {
+            self.value_subst.insert(v, value.clone());
+            if let Some(inputs) = &mut self.inputs {
+                if let Some(slot) = inputs.get_mut(v as usize) {
+                    slot.value = value;
+                }
+            }
+        }
+    }
+    /// The `Dim` standing in for a synthesized input's rank, minted once
+    /// per input and reused on later lookups, so e.g. two `Shape` calls on
+    /// the same pending argument agree on which rank it has.
+    fn rank_var(&mut self, v: u32) -> Dim {
+        if let Some(dim) = self.rank_vars.get(&v) {
+            return dim.clone();
+        }
+        let dim = self.fresh_dim();
+        self.rank_vars.insert(v, dim.clone());
+        dim
+    }
+    /// Commits a synthesized input that has no shape yet to a concrete
+    /// `rank`, filled with fresh dims, and records the result back onto the
+    /// input so later uses of the same argument agree with it. Errors if
+    /// `ty` isn't a synthesized input at all (just a value whose shape is
+    /// genuinely unknown, which can't be retroactively constrained).
+    fn ensure_rank(&mut self, ty: &mut Type, rank: usize) -> TypeResult<()> {
+        let ValueType::Var(v) = ty.value.clone() else {
+            return Err(TypeErrorKind::Mismatch {
+                expected: Type::new(ValueType::Unknown, []),
+                found: ty.clone(),
+            });
+        };
+        let shape: TypeShape = (0..rank).map(|_| self.fresh_dim()).collect();
+        ty.shape = Some(shape.clone());
+        if let Some(inputs) = &mut self.inputs {
+            if let Some(slot) = inputs.get_mut(v as usize) {
+                slot.shape = Some(shape);
+            }
+        }
+        Ok(())
+    }
+    /// Mints a fresh, as-yet-unbound shape variable.
+    fn fresh_dim(&mut self) -> Dim {
+        let var = self.next_var;
+        self.next_var += 1;
+        Dim::Var(var)
+    }
+    /// Follows `dim` through the substitution map until it reaches a
+    /// concrete extent or an unbound variable.
+    fn resolve_dim(&self, dim: Dim) -> Dim {
+        match dim {
+            Dim::Var(v) => match self.subst.get(&v) {
+                Some(bound) => self.resolve_dim(bound.clone()),
+                None => Dim::Var(v),
+            },
+            known => known,
+        }
+    }
+    /// Unifies two dimensions, binding any unresolved [`Dim::Var`] in
+    /// `self.subst` to the other side.
+    fn unify_dim(&mut self, a: Dim, b: Dim) -> TypeResult<Dim> {
+        let a = self.resolve_dim(a);
+        let b = self.resolve_dim(b);
+        match (a, b) {
+            (Dim::Known(x), Dim::Known(y)) => {
+                if x == y {
+                    Ok(Dim::Known(x))
+                } else {
+                    Err(TypeErrorKind::ShapeConflict {
+                        a: Dim::Known(x),
+                        b: Dim::Known(y),
+                    })
+                }
+            }
+            // A var unifying with itself must not bind, or `resolve_dim`
+            // would loop forever chasing `subst[v] == Var(v)`.
+            (Dim::Var(v), Dim::Var(w)) if v == w => Ok(Dim::Var(v)),
+            (Dim::Var(v), other) | (other, Dim::Var(v)) => {
+                self.subst.insert(v, other.clone());
+                Ok(other)
+            }
+        }
+    }
+    /// Unifies two shapes of equal rank dimension-by-dimension.
+    fn unify_shape(&mut self, a: TypeShape, b: TypeShape) -> TypeResult<TypeShape> {
+        if a.len() != b.len() {
+            return Err(TypeErrorKind::RankMismatch {
+                expected: a.len(),
+                found: b.len(),
+            });
+        }
+        a.into_iter()
+            .zip(b)
+            .map(|(x, y)| self.unify_dim(x, y))
+            .collect()
+    }
+    /// Broadcasts two operand shapes for a pervasive dyadic primitive: a
+    /// scalar (rank 0) broadcasts against any shape, and otherwise the
+    /// higher-rank shape wins, unifying the overlapping trailing dims with
+    /// the shorter shape.
+    fn broadcast_shapes(
+        &mut self,
+        a: Option<TypeShape>,
+        b: Option<TypeShape>,
+    ) -> TypeResult<Option<TypeShape>> {
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Ok(None),
+        };
+        if a.is_empty() {
+            return Ok(Some(b));
+        }
+        if b.is_empty() {
+            return Ok(Some(a));
+        }
+        let (mut longer, shorter) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+        let offset = longer.len() - shorter.len();
+        for (i, dim) in shorter.into_iter().enumerate() {
+            longer[offset + i] = self.unify_dim(longer[offset + i].clone(), dim)?;
+        }
+        Ok(Some(longer))
     }
 }